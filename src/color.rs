@@ -0,0 +1,243 @@
+use anyhow::{bail, Result};
+use crossterm::style;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Color {
+    pub fn from_rgb(r: u8, g: u8, b: u8) -> Self {
+        Self { r, g, b }
+    }
+
+    pub fn from_hexstring(hexstring: &str) -> Result<Self> {
+        let hexstring = hexstring.strip_prefix('#').unwrap_or(hexstring);
+        if hexstring.len() != 6 {
+            bail!("color must be a 6 digit hex string, got {hexstring:?}");
+        }
+
+        let r = u8::from_str_radix(&hexstring[0..2], 16)?;
+        let g = u8::from_str_radix(&hexstring[2..4], 16)?;
+        let b = u8::from_str_radix(&hexstring[4..6], 16)?;
+
+        Ok(Self { r, g, b })
+    }
+
+    pub fn as_hsl(&self) -> HslColor {
+        (*self).into()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HslColor {
+    pub h: f32,
+    pub s: f32,
+    pub l: f32,
+}
+
+impl HslColor {
+    pub fn new(h: f32, s: f32, l: f32) -> Self {
+        Self { h, s, l }
+    }
+}
+
+impl From<Color> for HslColor {
+    fn from(color: Color) -> Self {
+        let r = color.r as f32 / 255.0;
+        let g = color.g as f32 / 255.0;
+        let b = color.b as f32 / 255.0;
+
+        let max = r.max(g).max(b);
+        let min = r.min(g).min(b);
+        let delta = max - min;
+
+        let l = (max + min) / 2.0;
+
+        if delta == 0.0 {
+            return Self::new(0.0, 0.0, l * 100.0);
+        }
+
+        let s = if l > 0.5 {
+            delta / (2.0 - max - min)
+        } else {
+            delta / (max + min)
+        };
+
+        let h = if max == r {
+            ((g - b) / delta) % 6.0
+        } else if max == g {
+            (b - r) / delta + 2.0
+        } else {
+            (r - g) / delta + 4.0
+        };
+
+        let mut h = h * 60.0;
+        if h < 0.0 {
+            h += 360.0;
+        }
+
+        Self::new(h, s * 100.0, l * 100.0)
+    }
+}
+
+impl From<HslColor> for Color {
+    fn from(hsl: HslColor) -> Self {
+        let h = hsl.h.rem_euclid(360.0);
+        let s = hsl.s.clamp(0.0, 100.0) / 100.0;
+        let l = hsl.l.clamp(0.0, 100.0) / 100.0;
+
+        if s == 0.0 {
+            let v = (l * 255.0).round() as u8;
+            return Self::from_rgb(v, v, v);
+        }
+
+        let c = (1.0 - (2.0 * l - 1.0).abs()) * s;
+        let x = c * (1.0 - ((h / 60.0) % 2.0 - 1.0).abs());
+        let m = l - c / 2.0;
+
+        let (r1, g1, b1) = match h as u32 {
+            0..=59 => (c, x, 0.0),
+            60..=119 => (x, c, 0.0),
+            120..=179 => (0.0, c, x),
+            180..=239 => (0.0, x, c),
+            240..=299 => (x, 0.0, c),
+            _ => (c, 0.0, x),
+        };
+
+        Self::from_rgb(
+            ((r1 + m) * 255.0).round() as u8,
+            ((g1 + m) * 255.0).round() as u8,
+            ((b1 + m) * 255.0).round() as u8,
+        )
+    }
+}
+
+/// How many distinct colors the output terminal can display.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorDepth {
+    /// 24bit RGB, emitted as-is.
+    TrueColor,
+    /// The 256-color xterm palette.
+    Ansi256,
+    /// The 16 standard ANSI colors.
+    Ansi16,
+    /// No color at all.
+    None,
+}
+
+/// Mirrors the `--color` flag of common CLI tools such as `grep` or `ls`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorMode {
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorMode {
+    pub fn parse(value: &str) -> Result<Self> {
+        match value {
+            "auto" => Ok(Self::Auto),
+            "always" => Ok(Self::Always),
+            "never" => Ok(Self::Never),
+            other => bail!("invalid --color value {other:?}, expected auto, always or never"),
+        }
+    }
+}
+
+impl ColorDepth {
+    /// Resolve the effective color depth from the requested `mode`, the environment
+    /// and whether stdout is attached to a terminal.
+    pub fn detect(mode: ColorMode, is_tty: bool) -> Self {
+        match mode {
+            ColorMode::Never => ColorDepth::None,
+            ColorMode::Always => ColorDepth::from_env(),
+            ColorMode::Auto => {
+                if is_tty {
+                    ColorDepth::from_env()
+                } else {
+                    ColorDepth::None
+                }
+            }
+        }
+    }
+
+    fn from_env() -> Self {
+        let colorterm = std::env::var("COLORTERM").unwrap_or_default();
+        if colorterm.contains("truecolor") || colorterm.contains("24bit") {
+            return ColorDepth::TrueColor;
+        }
+
+        let term = std::env::var("TERM").unwrap_or_default();
+        if term.contains("256color") {
+            return ColorDepth::Ansi256;
+        }
+
+        ColorDepth::Ansi16
+    }
+}
+
+/// Quantize `color` down to whatever `depth` the terminal actually supports, returning
+/// `None` when no color should be emitted at all.
+pub fn quantize(color: Color, depth: ColorDepth) -> Option<style::Color> {
+    match depth {
+        ColorDepth::TrueColor => Some(style::Color::Rgb {
+            r: color.r,
+            g: color.g,
+            b: color.b,
+        }),
+        ColorDepth::Ansi256 => Some(style::Color::AnsiValue(quantize_256(color))),
+        ColorDepth::Ansi16 => Some(quantize_16(color)),
+        ColorDepth::None => None,
+    }
+}
+
+fn quantize_256(color: Color) -> u8 {
+    let (r, g, b) = (color.r as i32, color.g as i32, color.b as i32);
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+
+    // Near-grayscale colors look better on the dedicated grayscale ramp than
+    // on the 6x6x6 color cube.
+    if max - min < 8 {
+        let luma = (r + g + b) / 3;
+        let index = (((luma - 8) as f32 / 10.0).round() as i32).clamp(0, 23);
+        return (232 + index) as u8;
+    }
+
+    let q = |c: i32| ((c as f32 * 5.0 / 255.0).round() as i32).clamp(0, 5);
+    (16 + 36 * q(r) + 6 * q(g) + q(b)) as u8
+}
+
+fn quantize_16(color: Color) -> style::Color {
+    const PALETTE: [(style::Color, (i32, i32, i32)); 16] = [
+        (style::Color::Black, (0, 0, 0)),
+        (style::Color::DarkRed, (128, 0, 0)),
+        (style::Color::DarkGreen, (0, 128, 0)),
+        (style::Color::DarkYellow, (128, 128, 0)),
+        (style::Color::DarkBlue, (0, 0, 128)),
+        (style::Color::DarkMagenta, (128, 0, 128)),
+        (style::Color::DarkCyan, (0, 128, 128)),
+        (style::Color::Grey, (192, 192, 192)),
+        (style::Color::DarkGrey, (128, 128, 128)),
+        (style::Color::Red, (255, 0, 0)),
+        (style::Color::Green, (0, 255, 0)),
+        (style::Color::Yellow, (255, 255, 0)),
+        (style::Color::Blue, (0, 0, 255)),
+        (style::Color::Magenta, (255, 0, 255)),
+        (style::Color::Cyan, (0, 255, 255)),
+        (style::Color::White, (255, 255, 255)),
+    ];
+
+    let (r, g, b) = (color.r as i32, color.g as i32, color.b as i32);
+    PALETTE
+        .iter()
+        .min_by_key(|(_, (pr, pg, pb))| {
+            let (dr, dg, db) = (r - pr, g - pg, b - pb);
+            dr * dr + dg * dg + db * db
+        })
+        .map(|(c, _)| *c)
+        .expect("palette is non-empty")
+}
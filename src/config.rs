@@ -0,0 +1,106 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+
+use crate::color::Color;
+
+pub const DEFAULT_CHARSET: &str =
+    "ﾊﾐﾋｰｳｼﾅﾓﾆｻﾜﾂｵﾘｱﾎﾃﾏｹﾒｴｶｷﾑﾕﾗｾﾈｽﾀﾇﾍｦｲｸｺｿﾁﾄﾉﾌﾔﾖﾙﾚﾛﾝ012345789Z:.\"=*+-<>¦╌ç";
+
+/// Everything that gives a waterfall its look: what color it falls in, what
+/// color (if any) the leading glyph flashes, how quickly trails fade, and
+/// which characters are drawn from.
+#[derive(Clone)]
+pub struct Theme {
+    pub base_color: Color,
+    pub head_color: Option<Color>,
+    pub fade_factor: f32,
+    pub charset: String,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            base_color: Color::from_rgb(0, 255, 43),
+            head_color: None,
+            fade_factor: 0.90,
+            charset: DEFAULT_CHARSET.to_string(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct RawTheme {
+    base_color: String,
+    head_color: Option<String>,
+    #[serde(default = "default_fade_factor")]
+    fade_factor: f32,
+    charset: Option<String>,
+}
+
+fn default_fade_factor() -> f32 {
+    0.90
+}
+
+#[derive(Deserialize, Default)]
+struct RawConfig {
+    #[serde(default, rename = "theme")]
+    themes: HashMap<String, RawTheme>,
+}
+
+pub struct Config {
+    themes: HashMap<String, Theme>,
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("read config file {}", path.display()))?;
+        let raw: RawConfig = toml::from_str(&contents)
+            .with_context(|| format!("parse config file {}", path.display()))?;
+
+        let mut themes = HashMap::with_capacity(raw.themes.len());
+        for (name, raw_theme) in raw.themes {
+            let base_color = Color::from_hexstring(&raw_theme.base_color)
+                .with_context(|| format!("theme {name:?} has an invalid base_color"))?;
+            let head_color = raw_theme
+                .head_color
+                .as_deref()
+                .map(Color::from_hexstring)
+                .transpose()
+                .with_context(|| format!("theme {name:?} has an invalid head_color"))?;
+
+            let charset = raw_theme.charset.unwrap_or_else(|| DEFAULT_CHARSET.to_string());
+            if charset.is_empty() {
+                bail!("theme {name:?} has an empty charset");
+            }
+
+            themes.insert(
+                name,
+                Theme {
+                    base_color,
+                    head_color,
+                    fade_factor: raw_theme.fade_factor,
+                    charset,
+                },
+            );
+        }
+
+        Ok(Self { themes })
+    }
+
+    pub fn theme(&self, name: &str) -> Option<Theme> {
+        self.themes.get(name).cloned()
+    }
+}
+
+/// The default location searched for a config file, `<platform config
+/// dir>/green_rust/config.toml`, or `None` if the platform has no notion of
+/// one.
+pub fn default_config_path() -> Option<PathBuf> {
+    directories::ProjectDirs::from("", "", "green_rust")
+        .map(|dirs| dirs.config_dir().join("config.toml"))
+}
@@ -1,18 +1,24 @@
 mod color;
+mod config;
 
 use std::io::Write;
+use std::path::PathBuf;
 use std::time::Duration;
 use std::time::SystemTime;
 
 use anyhow::{Context, Result};
 use color::Color;
+use color::ColorDepth;
+use color::ColorMode;
 use color::HslColor;
-use crossterm::{cursor, queue, style, terminal};
+use config::{Config, Theme};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyModifiers};
+use crossterm::{cursor, queue, style, terminal, tty::IsTty};
 use rand::{Rng, RngCore};
 use rand_xoshiro::rand_core::SeedableRng;
 use rand_xoshiro::Xoshiro256PlusPlus;
 
-#[derive(Clone)]
+#[derive(Clone, PartialEq)]
 struct Glyph {
     character: char,
     color: Color,
@@ -23,13 +29,12 @@ impl Glyph {
         Self { character, color }
     }
 
-    fn new_random<R: Rng>(rand: &mut R, color: Color) -> Self {
-        let characters = "ﾊﾐﾋｰｳｼﾅﾓﾆｻﾜﾂｵﾘｱﾎﾃﾏｹﾒｴｶｷﾑﾕﾗｾﾈｽﾀﾇﾍｦｲｸｺｿﾁﾄﾉﾌﾔﾖﾙﾚﾛﾝ012345789Z:.\"=*+-<>¦╌ç";
+    fn new_random<R: Rng>(rand: &mut R, color: Color, charset: &str) -> Self {
         Self {
             // @TODO: Don't use chars iterator to count chars here every time.
-            character: characters
+            character: charset
                 .chars()
-                .nth(rand.gen_range(0..characters.chars().count()))
+                .nth(rand.gen_range(0..charset.chars().count()))
                 .unwrap(),
             color,
         }
@@ -42,103 +47,252 @@ impl Glyph {
         }
     }
 
-    fn render<W: Write>(&self, out: &mut W) -> Result<()> {
-        queue!(
-            out,
-            style::SetForegroundColor(style::Color::Rgb {
-                r: self.color.r,
-                g: self.color.g,
-                b: self.color.b
-            })
-        )?;
+    fn render<W: Write>(&self, out: &mut W, depth: ColorDepth) -> Result<()> {
+        if let Some(color) = color::quantize(self.color, depth) {
+            queue!(out, style::SetForegroundColor(color))?;
+        }
         queue!(out, style::Print(self.character.to_string())).context("write glyph to output")?;
         Ok(())
     }
 
-    fn fade_color(&mut self) {
+    fn fade_color(&mut self, fade_factor: f32, hue_drift: f32) {
         let hsl = self.color.as_hsl();
-        let new_color = HslColor::new(hsl.h, hsl.s * 0.90, hsl.l * 0.90);
+
+        // Once a cell has hit the fade floor, stop drifting its hue too, or it
+        // would keep changing forever and defeat the dirty-cell diffing in
+        // `MatrixWaterfall::render`.
+        let already_at_floor = hsl.s <= 10.0 && hsl.l <= 10.0;
+        let hue = if already_at_floor {
+            hsl.h
+        } else {
+            (hsl.h + hue_drift).rem_euclid(360.0)
+        };
+
+        let new_color = HslColor::new(hue, hsl.s * fade_factor, hsl.l * fade_factor);
         if new_color.s < 10.0 || new_color.l < 10.0 {
-            self.color = HslColor::new(hsl.h, 10.0, 10.0).into();
+            self.color = HslColor::new(hue, 10.0, 10.0).into();
         } else {
             self.color = new_color.into();
         }
     }
 }
 
+/// How the waterfall derives each cell's color from the theme's base color.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Pattern {
+    /// Every column uses the theme's base color as-is.
+    Solid,
+    /// Each column's trail drifts in hue as it fades, reading as a vertical
+    /// gradient.
+    Gradient,
+    /// Each column is offset in hue from the last, spanning the spectrum
+    /// across the width of the terminal.
+    Rainbow,
+}
+
+/// Degrees of hue shift applied to a glyph each time it fades in `Pattern::Gradient`.
+const GRADIENT_HUE_DRIFT_PER_TICK: f32 = 4.0;
+
+/// The static hue offset and per-tick hue drift a column at `x` of `width`
+/// should use for `pattern`.
+fn pattern_hue_params(pattern: Pattern, x: u16, width: u16) -> (f32, f32) {
+    match pattern {
+        Pattern::Solid => (0.0, 0.0),
+        Pattern::Gradient => (0.0, GRADIENT_HUE_DRIFT_PER_TICK),
+        Pattern::Rainbow => (x as f32 * (360.0 / width.max(1) as f32), 0.0),
+    }
+}
+
 #[derive(Clone)]
 struct Column {
     height: u16,
-    base_color: Color,
+    theme: Theme,
     glyphs: Vec<Glyph>,
     active_index: usize,
+    /// Static hue offset (in degrees) added to every glyph in this column,
+    /// used by `Pattern::Rainbow`.
+    hue_offset: f32,
+    /// Hue drift (in degrees) applied to a glyph each time it fades, used by
+    /// `Pattern::Gradient`.
+    hue_drift: f32,
 }
 
 impl Column {
-    fn new(height: u16, base_color: Color) -> Self {
+    fn new(height: u16, theme: Theme, hue_offset: f32, hue_drift: f32) -> Self {
         Self {
             height,
-            base_color,
             glyphs: vec![Glyph::empty(); height as usize],
             active_index: 0,
+            theme,
+            hue_offset,
+            hue_drift,
         }
     }
 
-    fn render<W: Write>(&self, out: &mut W, y: u16) -> Result<()> {
-        self.glyphs[y as usize].render(out)?;
+    fn render<W: Write>(&self, out: &mut W, y: u16, depth: ColorDepth) -> Result<()> {
+        self.glyphs[y as usize].render(out, depth)?;
         Ok(())
     }
 
     fn step<R: Rng>(&mut self, rand: &mut R) {
         for glyph in &mut self.glyphs {
-            glyph.fade_color();
+            glyph.fade_color(self.theme.fade_factor, self.hue_drift);
         }
 
         if self.active_index == 0 && rand.gen::<f32>() > 0.1 {
             return;
         }
 
-        self.glyphs[self.active_index] = Glyph::new_random(rand, self.base_color);
+        let base_hsl = self.theme.base_color.as_hsl();
+        let hue = (base_hsl.h + self.hue_offset).rem_euclid(360.0);
+        // Within a stream, the glyph written on the previous step drops back to
+        // the column's own color, so only the newest glyph stands out.
+        if self.active_index > 0 {
+            self.glyphs[self.active_index - 1].color = HslColor::new(hue, base_hsl.s, base_hsl.l).into();
+        }
+        // The leading glyph flashes in the theme's head color, or near-white in
+        // the column's hue when the theme has none.
+        let head_color = self.theme.head_color.unwrap_or_else(|| HslColor::new(hue, 15.0, 95.0).into());
+
+        self.glyphs[self.active_index] = Glyph::new_random(rand, head_color, &self.theme.charset);
         self.active_index += 1;
 
         if self.active_index >= self.height as usize {
             self.active_index = 0;
         }
     }
+
+    /// Adapts this column to a new terminal height and hue parameters,
+    /// keeping as much of its existing trail as still fits.
+    fn resized(&self, height: u16, hue_offset: f32, hue_drift: f32) -> Self {
+        let mut glyphs = self.glyphs.clone();
+        glyphs.resize(height as usize, Glyph::empty());
+
+        Self {
+            height,
+            theme: self.theme.clone(),
+            glyphs,
+            active_index: self.active_index.min(height.saturating_sub(1) as usize),
+            hue_offset,
+            hue_drift,
+        }
+    }
 }
 
 struct MatrixWaterfall {
     width: u16,
     height: u16,
-    base_color: Color,
+    theme: Theme,
+    pattern: Pattern,
     columns: Vec<Column>,
+    color_depth: ColorDepth,
+    /// Whether stdout is not a terminal, so cursor/escape sequences would show
+    /// up as garbage and rows must be separated with plain `\n` instead.
+    plain: bool,
+    /// What was actually drawn to the terminal on the previous frame, indexed by
+    /// `y * width + x`, so `render` only has to touch cells that changed.
+    previous: Vec<Glyph>,
+    /// Forces the next `render` to repaint every cell, e.g. on the very first
+    /// frame or after the terminal was resized.
+    force_full_redraw: bool,
 }
 
 impl MatrixWaterfall {
-    fn new(width: u16, height: u16, base_color: Color) -> Self {
+    fn new(
+        width: u16,
+        height: u16,
+        theme: Theme,
+        pattern: Pattern,
+        color_depth: ColorDepth,
+        plain: bool,
+    ) -> Self {
+        let columns = (0..width)
+            .map(|x| {
+                let (hue_offset, hue_drift) = pattern_hue_params(pattern, x, width);
+                Column::new(height, theme.clone(), hue_offset, hue_drift)
+            })
+            .collect();
+
         Self {
             width,
             height,
-            base_color,
-            columns: vec![Column::new(height, base_color); width as usize],
+            columns,
+            theme,
+            pattern,
+            color_depth,
+            plain,
+            previous: vec![Glyph::empty(); width as usize * height as usize],
+            force_full_redraw: true,
         }
     }
 
-    fn render<W: Write>(&self, out: &mut W) -> Result<()> {
-        queue!(out, cursor::Hide)?;
-        queue!(out, cursor::MoveTo(0, 0))?;
-        queue!(
-            out,
-            style::SetBackgroundColor(style::Color::Rgb { r: 0, g: 0, b: 0 })
-        )?;
+    fn cell_index(&self, x: u16, y: u16) -> usize {
+        y as usize * self.width as usize + x as usize
+    }
+
+    fn cell_changed(&self, x: u16, y: u16) -> bool {
+        self.force_full_redraw
+            || self.columns[x as usize].glyphs[y as usize] != self.previous[self.cell_index(x, y)]
+    }
+
+    fn render<W: Write>(&mut self, out: &mut W) -> Result<()> {
+        if self.plain {
+            for y in 0..self.height {
+                for column in &self.columns {
+                    column.render(out, y, self.color_depth)?;
+                }
+                queue!(out, style::Print("\n"))?;
+            }
+            out.flush().context("flush output")?;
+            return Ok(());
+        }
+
+        if self.force_full_redraw {
+            queue!(
+                out,
+                style::SetBackgroundColor(style::Color::Rgb { r: 0, g: 0, b: 0 })
+            )?;
+        }
+
+        let mut last_emitted_color = None;
 
         for y in 0..self.height {
-            for column in &self.columns {
-                column.render(out, y)?;
+            let mut x = 0u16;
+            while x < self.width {
+                if !self.cell_changed(x, y) {
+                    x += 1;
+                    continue;
+                }
+
+                // Group contiguous changed cells on this row into a single run so
+                // we only need one cursor move for all of them.
+                let run_start = x;
+                while x < self.width && self.cell_changed(x, y) {
+                    x += 1;
+                }
+                let run_end = x;
+
+                queue!(out, cursor::MoveTo(run_start, y))?;
+                for cx in run_start..run_end {
+                    let glyph = &self.columns[cx as usize].glyphs[y as usize];
+                    if last_emitted_color != Some(glyph.color) {
+                        if let Some(color) = color::quantize(glyph.color, self.color_depth) {
+                            queue!(out, style::SetForegroundColor(color))?;
+                        }
+                        last_emitted_color = Some(glyph.color);
+                    }
+                    queue!(out, style::Print(glyph.character.to_string()))?;
+                }
             }
         }
-        queue!(out, style::ResetColor)?;
-        queue!(out, cursor::Show)?;
+
+        for x in 0..self.width {
+            for y in 0..self.height {
+                self.previous[self.cell_index(x, y)] = self.columns[x as usize].glyphs[y as usize].clone();
+            }
+        }
+
+        self.force_full_redraw = false;
         out.flush().context("flush output")?;
         Ok(())
     }
@@ -148,22 +302,87 @@ impl MatrixWaterfall {
             column.step(rand);
         }
     }
+
+    /// Rebuilds the grid for a new terminal size, preserving the columns (and
+    /// their trails) that overlap with the previous size.
+    fn resize(&mut self, width: u16, height: u16) {
+        // Some terminals/multiplexers briefly report a height of 0 mid-resize;
+        // a zero-height column has no glyphs for `step` to index into.
+        let height = height.max(1);
+        self.columns = (0..width)
+            .map(|x| {
+                let (hue_offset, hue_drift) = pattern_hue_params(self.pattern, x, width);
+                match self.columns.get(x as usize) {
+                    Some(column) => column.resized(height, hue_offset, hue_drift),
+                    None => Column::new(height, self.theme.clone(), hue_offset, hue_drift),
+                }
+            })
+            .collect();
+        self.width = width;
+        self.height = height;
+        self.previous = vec![Glyph::empty(); width as usize * height as usize];
+        self.force_full_redraw = true;
+    }
 }
 
 fn usage(command: &str) {
     eprintln!("Usage:");
     eprintln!("  {command} [OPTIONS]");
     eprintln!("Options:");
-    eprintln!("  --color HEXCOLOR");
+    eprintln!("  --base-color HEXCOLOR");
     eprintln!("  --speed UPDATES_PER_SEC");
+    eprintln!("  --color auto|always|never");
+    eprintln!("  --theme NAME");
+    eprintln!("  --config PATH");
+    eprintln!("  --gradient");
+    eprintln!("  --rainbow");
+    eprintln!("Keys (while running):");
+    eprintln!("  q, Esc    quit");
+    eprintln!("  space     pause/resume");
+    eprintln!("  +, -      adjust speed");
+    eprintln!("  r         reseed");
+}
+
+fn random_seed() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .expect("time to have passed since UNIX_EPOCH")
+        .as_micros() as u64
+}
+
+/// Puts the terminal into raw mode and an alternate screen for the duration of
+/// the animation, restoring it on drop so Ctrl-C or a panic never leaves the
+/// user's shell in a broken state.
+struct TerminalGuard;
+
+impl TerminalGuard {
+    fn new<W: Write>(out: &mut W) -> Result<Self> {
+        terminal::enable_raw_mode().context("enable raw mode")?;
+        queue!(out, terminal::EnterAlternateScreen, cursor::Hide).context("enter alternate screen")?;
+        out.flush().context("flush output")?;
+        Ok(Self)
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let mut out = std::io::stdout();
+        let _ = queue!(out, style::ResetColor, cursor::Show, terminal::LeaveAlternateScreen);
+        let _ = out.flush();
+        let _ = terminal::disable_raw_mode();
+    }
 }
 
 fn main() -> Result<()> {
     let mut args = std::env::args();
     let command = args.next().expect("args should have at least command");
 
-    let mut base_color = Color::from_rgb(0, 255, 43);
+    let mut base_color_override = None;
     let mut speed: u32 = 13;
+    let mut color_mode = ColorMode::Auto;
+    let mut config_path_override = None;
+    let mut theme_name = None;
+    let mut pattern = Pattern::Solid;
 
     while let Some(arg) = args.next() {
         match arg.as_str() {
@@ -171,10 +390,14 @@ fn main() -> Result<()> {
                 usage(&command);
                 std::process::exit(0);
             }
-            "--color" => {
-                let hexcolor = args.next().expect("hex color provided after --color");
-                base_color = Color::from_hexstring(hexcolor.as_str())
-                    .expect("provided color to be valid hexstring");
+            "--gradient" => pattern = Pattern::Gradient,
+            "--rainbow" => pattern = Pattern::Rainbow,
+            "--base-color" => {
+                let hexcolor = args.next().expect("hex color provided after --base-color");
+                base_color_override = Some(
+                    Color::from_hexstring(hexcolor.as_str())
+                        .expect("provided color to be valid hexstring"),
+                );
             }
             "--speed" => {
                 speed = args
@@ -188,6 +411,22 @@ fn main() -> Result<()> {
                     std::process::exit(1);
                 }
             }
+            "--color" => {
+                let mode = args.next().expect("color mode provided after --color");
+                color_mode = ColorMode::parse(mode.as_str()).unwrap_or_else(|err| {
+                    eprintln!("{err}");
+                    usage(&command);
+                    std::process::exit(1);
+                });
+            }
+            "--theme" => {
+                theme_name = Some(args.next().expect("theme name provided after --theme"));
+            }
+            "--config" => {
+                config_path_override = Some(PathBuf::from(
+                    args.next().expect("config path provided after --config"),
+                ));
+            }
             _ => {
                 eprintln!("Unknown argument {arg}");
                 usage(&command);
@@ -196,22 +435,91 @@ fn main() -> Result<()> {
         }
     }
 
+    // An explicit `--config PATH` that doesn't exist is a user error; the
+    // auto-discovered default path is allowed to just not be there.
+    let config = match config_path_override {
+        Some(path) => {
+            if !path.exists() {
+                anyhow::bail!("--config {} does not exist", path.display());
+            }
+            Some(Config::load(&path).context("load config file")?)
+        }
+        None => config::default_config_path()
+            .filter(|path| path.exists())
+            .map(|path| Config::load(&path))
+            .transpose()
+            .context("load config file")?,
+    };
+
+    let mut theme = match (&config, &theme_name) {
+        (Some(config), Some(name)) => config
+            .theme(name)
+            .with_context(|| format!("no theme named {name:?} in config file"))?,
+        (None, Some(name)) => {
+            anyhow::bail!("--theme {name:?} given but no config file was found")
+        }
+        _ => Theme::default(),
+    };
+    if let Some(base_color) = base_color_override {
+        theme.base_color = base_color;
+    }
+
     let (width, height) = terminal::size().context("determine terminal size")?;
 
-    let mut waterfall = MatrixWaterfall::new(width, height, base_color);
     let mut stdout = std::io::stdout();
+    let is_tty = stdout.is_tty();
+    let color_depth = ColorDepth::detect(color_mode, is_tty);
+    // A pipe has no cursor to animate into, regardless of the requested color mode.
+    let animate = is_tty;
 
-    let seed = SystemTime::now()
-        .duration_since(SystemTime::UNIX_EPOCH)
-        .expect("time to have passed since UNIX_EPOCH")
-        .as_micros() as u64;
-    let mut rand = Xoshiro256PlusPlus::seed_from_u64(seed);
+    let mut waterfall = MatrixWaterfall::new(width, height, theme, pattern, color_depth, !is_tty);
+    let mut rand = Xoshiro256PlusPlus::seed_from_u64(random_seed());
+
+    // Without a terminal there's nothing to animate into: render a single
+    // plain-text frame and exit instead of looping forever into a pipe.
+    if !animate {
+        waterfall.render(&mut stdout)?;
+        return Ok(());
+    }
+
+    let _terminal_guard = TerminalGuard::new(&mut stdout)?;
 
-    let frame_wait = (1000f64 / speed as f64).round() as u64;
+    let mut paused = false;
+    let mut frame_wait = (1000f64 / speed as f64).round() as u64;
 
     loop {
-        waterfall.render(&mut stdout)?;
-        waterfall.step(&mut rand);
-        std::thread::sleep(Duration::from_millis(frame_wait));
+        if !paused {
+            waterfall.render(&mut stdout)?;
+            waterfall.step(&mut rand);
+        }
+
+        if event::poll(Duration::from_millis(frame_wait)).context("poll for terminal events")? {
+            match event::read().context("read terminal event")? {
+                Event::Resize(new_width, new_height) => {
+                    waterfall.resize(new_width, new_height);
+                }
+                Event::Key(KeyEvent { code, modifiers, .. }) => match code {
+                    // Raw mode turns Ctrl-C into a key event instead of a signal.
+                    KeyCode::Char('c') if modifiers.contains(KeyModifiers::CONTROL) => break,
+                    KeyCode::Char('q') | KeyCode::Esc => break,
+                    KeyCode::Char(' ') => paused = !paused,
+                    KeyCode::Char('+') => {
+                        speed = (speed + 1).min(120);
+                        frame_wait = (1000f64 / speed as f64).round() as u64;
+                    }
+                    KeyCode::Char('-') => {
+                        speed = speed.saturating_sub(1).max(1);
+                        frame_wait = (1000f64 / speed as f64).round() as u64;
+                    }
+                    KeyCode::Char('r') => {
+                        rand = Xoshiro256PlusPlus::seed_from_u64(random_seed());
+                    }
+                    _ => {}
+                },
+                _ => {}
+            }
+        }
     }
+
+    Ok(())
 }